@@ -0,0 +1,144 @@
+// A dynamic, incrementally-updatable variant of `COITree`.
+//
+// `COITree::new` bakes a fixed set of intervals into vEB order and is
+// thereafter immutable, so any change to the interval set forces a full
+// rebuild. `DynamicCOITree` instead keeps a small staging buffer of
+// recently-inserted intervals plus a stack of sealed, immutable `COITree`s
+// of geometrically increasing capacity, following the leveled-compaction
+// scheme used by LevelDB's `Version`. Level `i` holds up to
+// `STAGING_CAPACITY * LEVEL_GROWTH_RATIO^i` intervals; once the staging
+// buffer fills it is sealed into level 0, and whenever a level overflows it
+// is merged with every smaller level into a fresh tree at the next level
+// up. Queries touch the staging buffer by brute force and every sealed
+// level via the existing `query_recursion`, unioning results and filtering
+// out tombstoned intervals.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::{overlaps, query_recursion, COITree, IntervalMetadata, IntervalNode, NIL};
+
+/// Number of intervals held in the staging buffer before it is sealed into
+/// level 0. Kept small so brute-force scans of the buffer stay cheap.
+const STAGING_CAPACITY: usize = 64;
+
+/// Growth factor between successive levels.
+const LEVEL_GROWTH_RATIO: usize = 4;
+
+/// A dynamic variant of `COITree` supporting incremental `insert` and
+/// `remove` in addition to `query`, at the cost of touching `O(log n)`
+/// sealed levels per query instead of just one.
+pub struct DynamicCOITree<T>
+    where T: std::marker::Copy + IntervalMetadata + Eq + Hash {
+    staging: Vec<IntervalNode<T>>,
+    tombstones: HashSet<(i32, i32, T)>,
+    levels: Vec<Option<COITree<T>>>,
+}
+
+impl<T> DynamicCOITree<T>
+        where T: std::marker::Copy + IntervalMetadata + Eq + Hash {
+    pub fn new() -> DynamicCOITree<T> {
+        DynamicCOITree {
+            staging: Vec::new(),
+            tombstones: HashSet::new(),
+            levels: Vec::new(),
+        }
+    }
+
+    /// Capacity of `levels[i]` before it must be merged into `levels[i+1]`.
+    fn level_capacity(i: usize) -> usize {
+        STAGING_CAPACITY * LEVEL_GROWTH_RATIO.pow(i as u32)
+    }
+
+    pub fn insert(&mut self, first: i32, last: i32, metadata: T) {
+        // undo a pending removal of this exact interval, if any
+        self.tombstones.remove(&(first, last, metadata));
+
+        self.staging.push(IntervalNode {
+            first: first, last: last,
+            subtree_first: first, subtree_last: last,
+            left: NIL, right: NIL,
+            metadata: metadata,
+        });
+
+        if self.staging.len() >= STAGING_CAPACITY {
+            self.flush_staging();
+        }
+    }
+
+    pub fn remove(&mut self, first: i32, last: i32, metadata: T) {
+        let pos = self.staging.iter().position(|node| {
+            node.first == first && node.last == last && node.metadata == metadata
+        });
+
+        if let Some(pos) = pos {
+            self.staging.swap_remove(pos);
+        } else {
+            self.tombstones.insert((first, last, metadata));
+        }
+    }
+
+    pub fn query<F>(&self, first: i32, last: i32, mut visit: F)
+            where F: FnMut(&IntervalNode<T>) {
+        // `insert` clears a value's tombstone on reinsert but leaves any
+        // stale physical copy in place wherever it was last sealed, so the
+        // same value can be present, untombstoned, in more than one of
+        // staging and the levels at once (see the reinsert-after-cascade
+        // regression test, where the stale copy sits in a level above the
+        // one the reinsert flushes into). Levels are visited lowest
+        // (freshest) first, so dedup against every value already visited
+        // rather than just against `staging`, keeping only the first copy
+        // found.
+        let mut seen: HashSet<(i32, i32, T)> = HashSet::new();
+        for node in &self.staging {
+            let key = (node.first, node.last, node.metadata);
+            if seen.insert(key) && overlaps(node.first, node.last, first, last) {
+                visit(node);
+            }
+        }
+
+        for level in self.levels.iter().flatten() {
+            let mut visited = 0;
+            query_recursion(level.nodes(), 0, first, last, &mut visited, &mut |node| {
+                let key = (node.first, node.last, node.metadata);
+                if !self.tombstones.contains(&key) && seen.insert(key) {
+                    visit(node);
+                }
+            });
+        }
+    }
+
+    fn flush_staging(&mut self) {
+        let sealed = std::mem::replace(&mut self.staging, Vec::new());
+        self.seal_level(0, sealed);
+    }
+
+    // merge `nodes` into `levels[i]`, dropping any node whose value has
+    // since been tombstoned (pruning the tombstone along with it, since it
+    // has now done its job) and cascading the merge upward through every
+    // level that overflows as a result.
+    fn seal_level(&mut self, i: usize, mut nodes: Vec<IntervalNode<T>>) {
+        if let Some(existing) = self.levels.get_mut(i).and_then(Option::take) {
+            nodes.extend(existing.nodes().iter().cloned());
+        }
+
+        nodes.retain(|node| {
+            !self.tombstones.remove(&(node.first, node.last, node.metadata))
+        });
+
+        if i == self.levels.len() {
+            self.levels.push(Some(COITree::new(nodes)));
+        } else if nodes.len() > Self::level_capacity(i) {
+            self.seal_level(i + 1, nodes);
+        } else {
+            self.levels[i] = Some(COITree::new(nodes));
+        }
+    }
+}
+
+impl<T> Default for DynamicCOITree<T>
+        where T: std::marker::Copy + IntervalMetadata + Eq + Hash {
+    fn default() -> DynamicCOITree<T> {
+        DynamicCOITree::new()
+    }
+}
@@ -0,0 +1,78 @@
+// Streaming-iterator query API.
+//
+// `query` and `SortedQuerent::query` push results through an `FnMut`
+// visitor, which makes it impossible to lazily consume overlaps, stop early
+// cheaply, or zip two queries together. `OverlapIter` instead implements
+// `StreamingIterator` over the explicit-stack traversal sketched in the
+// commented-out `inlined_query`: push the root, and on each `advance` pop a
+// node, descend into children whose `subtree_first`/`subtree_last` overlap
+// the query, and surface the popped node when it itself overlaps. A
+// streaming iterator (rather than `Iterator`) avoids borrowing `self` once
+// per yielded item while still letting callers use `take_while` and friends.
+
+extern crate streaming_iterator;
+
+use streaming_iterator::StreamingIterator;
+
+use crate::{child_some, overlaps, IntervalMetadata, IntervalNode};
+
+/// A streaming iterator over the intervals in a `COITree` overlapping a
+/// query range, built by `COITree::overlaps`.
+pub struct OverlapIter<'a, T>
+    where T: std::marker::Copy + IntervalMetadata {
+    nodes: &'a [IntervalNode<T>],
+    stack: Vec<usize>,
+    first: i32,
+    last: i32,
+    current: Option<usize>,
+}
+
+impl<'a, T> OverlapIter<'a, T>
+        where T: std::marker::Copy + IntervalMetadata {
+    pub(crate) fn new(nodes: &'a [IntervalNode<T>], first: i32, last: i32) -> OverlapIter<'a, T> {
+        let mut stack = Vec::new();
+        if !nodes.is_empty() {
+            stack.push(0);
+        }
+
+        OverlapIter { nodes: nodes, stack: stack, first: first, last: last, current: None }
+    }
+}
+
+impl<'a, T> StreamingIterator for OverlapIter<'a, T>
+        where T: std::marker::Copy + IntervalMetadata {
+    type Item = IntervalNode<T>;
+
+    fn advance(&mut self) {
+        self.current = None;
+
+        while let Some(idx) = self.stack.pop() {
+            let node = &self.nodes[idx];
+
+            if let Some(right) = child_some(node.right) {
+                if overlaps(
+                        self.nodes[right].subtree_first, self.nodes[right].subtree_last,
+                        self.first, self.last) {
+                    self.stack.push(right);
+                }
+            }
+
+            if let Some(left) = child_some(node.left) {
+                if overlaps(
+                        self.nodes[left].subtree_first, self.nodes[left].subtree_last,
+                        self.first, self.last) {
+                    self.stack.push(left);
+                }
+            }
+
+            if overlaps(node.first, node.last, self.first, self.last) {
+                self.current = Some(idx);
+                break;
+            }
+        }
+    }
+
+    fn get(&self) -> Option<&IntervalNode<T>> {
+        self.current.map(|idx| &self.nodes[idx])
+    }
+}
@@ -1,40 +1,225 @@
 
 extern crate csv; // for parsing BED
+extern crate roaring; // for compressed bitmap query results
+extern crate bytemuck; // for zero-copy (de)serialization
+extern crate memmap; // for mapping prebuilt trees
+use roaring::RoaringBitmap;
+use bytemuck::{Pod, Zeroable};
 use std::cmp::{min, max};
 use std::collections::HashMap;
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::error::Error;
 use std::io;
+use std::io::Write;
+use std::mem::size_of;
 use std::time::Instant;
 
+mod dynamic;
+mod iter;
+mod serialize;
+
+pub use dynamic::DynamicCOITree;
+pub use iter::OverlapIter;
+pub use serialize::{DeserializeError, COITreeHeader};
+
 type GenericError = Box<Error>;
 
 
+// `left`/`right` point at child nodes by index, with `NIL` standing in for
+// `None`. This (rather than `Option<usize>`) is what lets `IntervalNode` be
+// `#[repr(C)]` and `Pod`: an `Option` has no stable on-disk layout, but a
+// sentinel index does, which is what makes `COITree::from_mmap` possible.
+const NIL: i32 = -1;
+
+#[inline]
+fn child_some(idx: i32) -> Option<usize> {
+    if idx == NIL { None } else { Some(idx as usize) }
+}
+
+#[inline]
+fn child_option(idx: Option<usize>) -> i32 {
+    match idx {
+        Some(i) => i as i32,
+        None => NIL,
+    }
+}
+
+// `IntervalMetadata` (rather than plain `Pod`) gates every metadata type
+// used crate-wide, not just for serialization, so that `COITree` can
+// transparently hold either an owned `Vec<IntervalNode<T>>` or a
+// memory-mapped one without the two cases needing different trait bounds.
+//
+// `Pod` alone isn't a strong enough bound for `metadata: T` here: the six
+// `i32` fields above it are 24 bytes, so `T` is safe to treat as `Pod` only
+// if its size and alignment leave no padding before or after it in
+// `IntervalNode`'s `#[repr(C)]` layout (e.g. `u16` pads the struct from 26
+// to 28 bytes). Padding bytes are uninitialized, and `COITree::serialize`
+// reads them out via `bytemuck::cast_slice`, which is UB for any
+// uninitialized byte. `IntervalMetadata` is `unsafe` precisely so that
+// implementing it for a new metadata type is an explicit, checked promise
+// that its layout leaves no such gap.
+pub unsafe trait IntervalMetadata: std::marker::Copy + Pod {}
+
+unsafe impl IntervalMetadata for () {}
+unsafe impl IntervalMetadata for u32 {}
+
 #[derive(Copy, Clone, Debug)]
-struct IntervalNode<T> where T: std::marker::Copy
+#[repr(C)]
+pub struct IntervalNode<T> where T: std::marker::Copy + IntervalMetadata
  {
-    first: i32,
-    last: i32,
+    pub first: i32,
+    pub last: i32,
 
     subtree_first: i32,
     subtree_last: i32,
 
-    left: Option<usize>,
-    right: Option<usize>,
+    left: i32,
+    right: i32,
+
+    pub metadata: T,
+}
+
+impl<T> IntervalNode<T> where T: std::marker::Copy + IntervalMetadata {
+    /// Build a leaf node ready to hand to `COITree::new`. `subtree_first`
+    /// and `subtree_last` start out equal to `first`/`last` and get
+    /// recomputed by `veb_order`, so callers never set them directly.
+    pub fn new(first: i32, last: i32, metadata: T) -> IntervalNode<T> {
+        IntervalNode {
+            first: first, last: last,
+            subtree_first: first, subtree_last: last,
+            left: NIL, right: NIL,
+            metadata: metadata,
+        }
+    }
+}
 
-    metadata: T,
+// Sound because `IntervalMetadata` only admits metadata types already
+// checked to leave `IntervalNode`'s layout padding-free.
+unsafe impl<T> Zeroable for IntervalNode<T> where T: std::marker::Copy + IntervalMetadata {}
+unsafe impl<T> Pod for IntervalNode<T> where T: std::marker::Copy + IntervalMetadata {}
+
+
+// Nodes are either owned (built by `COITree::new`) or read from a
+// memory-mapped, previously `serialize`d tree (`COITree::from_mmap`), whose
+// `Mmap` the tree then owns. The
+// mapped case reinterprets the mapped bytes on every access rather than
+// storing the resulting slice, which avoids `COITree` needing to be
+// self-referential.
+enum NodeStorage<T> where T: std::marker::Copy + IntervalMetadata {
+    Owned(Vec<IntervalNode<T>>),
+    Mapped(memmap::Mmap, std::marker::PhantomData<T>),
 }
 
 
-struct COITree<T>  where T: std::marker::Copy {
-    nodes: Vec<IntervalNode<T>>
+pub struct COITree<T>  where T: std::marker::Copy + IntervalMetadata {
+    storage: NodeStorage<T>,
 }
 
 
-impl<T> COITree<T> where T: std::marker::Copy {
+impl<T> COITree<T> where T: std::marker::Copy + IntervalMetadata {
     pub fn new(mut nodes: Vec<IntervalNode<T>>) -> COITree<T> {
         veb_order(&mut nodes);
-        return COITree { nodes: nodes };
+        return COITree { storage: NodeStorage::Owned(nodes) };
+    }
+
+    fn nodes(&self) -> &[IntervalNode<T>] {
+        match &self.storage {
+            NodeStorage::Owned(nodes) => nodes,
+            NodeStorage::Mapped(mmap, _) =>
+                bytemuck::cast_slice(&mmap[size_of::<COITreeHeader>()..]),
+        }
+    }
+
+    // Stream overlapping intervals rather than visiting them through a
+    // callback, so callers can lazily consume, zip, or break out of a query.
+    pub fn overlaps(&self, first: i32, last: i32) -> OverlapIter<T> {
+        return OverlapIter::new(self.nodes(), first, last);
+    }
+
+    // Total number of query positions covered by at least one interval.
+    pub fn covered_bases(&self, first: i32, last: i32) -> i64 {
+        let mut hits: Vec<(i32, i32)> = Vec::new();
+        let mut visited = 0;
+        query_recursion(self.nodes(), 0, first, last, &mut visited, &mut |node| {
+            hits.push((max(first, node.first), min(last, node.last)));
+        });
+        hits.sort_unstable();
+
+        let mut merged: Option<(i32, i32)> = None;
+        let mut covered = 0i64;
+        for (start, end) in hits {
+            merged = match merged {
+                None => Some((start, end)),
+                Some((cur_start, cur_end)) => {
+                    if start > cur_end + 1 {
+                        covered += (cur_end - cur_start + 1) as i64;
+                        Some((start, end))
+                    } else {
+                        Some((cur_start, max(cur_end, end)))
+                    }
+                }
+            };
+        }
+        if let Some((cur_start, cur_end)) = merged {
+            covered += (cur_end - cur_start + 1) as i64;
+        }
+
+        return covered;
+    }
+
+    // Sum, over every overlapping interval, of the number of query
+    // positions that interval covers.
+    pub fn overlap_bases(&self, first: i32, last: i32) -> i64 {
+        let mut total = 0i64;
+        let mut visited = 0;
+        query_recursion(self.nodes(), 0, first, last, &mut visited, &mut |node| {
+            total += (min(last, node.last) - max(first, node.first) + 1) as i64;
+        });
+        return total;
+    }
+
+    // Number of overlapping intervals covering each position in
+    // `first..=last`, computed as a difference array over the query window
+    // so it stays O(hits + span) rather than allocating a node per base.
+    pub fn depth_histogram(&self, first: i32, last: i32) -> Vec<u32> {
+        let span = (last - first + 1) as usize;
+        let mut diff = vec![0i32; span + 1];
+
+        let mut visited = 0;
+        query_recursion(self.nodes(), 0, first, last, &mut visited, &mut |node| {
+            let start = (max(first, node.first) - first) as usize;
+            let end = (min(last, node.last) - first) as usize;
+            diff[start] += 1;
+            diff[end + 1] -= 1;
+        });
+
+        let mut histogram = Vec::with_capacity(span);
+        let mut depth = 0i32;
+        for d in diff.into_iter().take(span) {
+            depth += d;
+            histogram.push(depth as u32);
+        }
+        return histogram;
+    }
+}
+
+
+// When metadata is an id, query results can be accumulated into a
+// compressed bitmap instead of a `Vec`, so that hit sets from different
+// queries (and different chromosomes) can be combined with near-linear-time
+// set algebra (`&`, `|`, `-`) rather than re-sorting and merging `Vec<u32>`s.
+impl COITree<u32> {
+    pub fn query_bitmap(&self, first: i32, last: i32, bitmap: &mut RoaringBitmap) {
+        let mut visited = 0;
+        query_recursion(self.nodes(), 0, first, last, &mut visited, &mut |node| {
+            bitmap.insert(node.metadata);
+        });
+    }
+
+    pub fn query_count_bitmap(&self, first: i32, last: i32) -> u64 {
+        let mut bitmap = RoaringBitmap::new();
+        self.query_bitmap(first, last, &mut bitmap);
+        return bitmap.len();
     }
 }
 
@@ -167,17 +352,17 @@ fn veb_order_recursion(
 
 // traverse the tree filling the subtree_first and subtree_last fields.
 fn compute_subtree_sizes<T>(nodes: &mut [IntervalNode<T>], root_idx: usize)
-        where T: std::marker::Copy {
+        where T: std::marker::Copy + IntervalMetadata {
     let mut subtree_first = nodes[root_idx].first;
     let mut subtree_last = nodes[root_idx].last;
 
-    if let Some(left) = nodes[root_idx].left {
+    if let Some(left) = child_some(nodes[root_idx].left) {
         compute_subtree_sizes(nodes, left);
         subtree_first = min(subtree_first, nodes[left].subtree_first);
         subtree_last  = max(subtree_last, nodes[left].subtree_last);
     }
 
-    if let Some(right) = nodes[root_idx].right {
+    if let Some(right) = child_some(nodes[root_idx].right) {
         compute_subtree_sizes(nodes, right);
         subtree_first = min(subtree_first, nodes[right].subtree_first);
         subtree_last  = max(subtree_last, nodes[right].subtree_last);
@@ -189,15 +374,15 @@ fn compute_subtree_sizes<T>(nodes: &mut [IntervalNode<T>], root_idx: usize)
 
 
 fn compute_tree_size<T>(nodes: &mut [IntervalNode<T>], root_idx: usize) -> usize
-        where T: std::marker::Copy {
+        where T: std::marker::Copy + IntervalMetadata {
 
     let mut subtree_size = 1;
 
-    if let Some(left) = nodes[root_idx].left {
+    if let Some(left) = child_some(nodes[root_idx].left) {
         subtree_size += compute_tree_size(nodes, left);
     }
 
-    if let Some(right) = nodes[root_idx].right {
+    if let Some(right) = child_some(nodes[root_idx].right) {
         subtree_size += compute_tree_size(nodes, right);
     }
 
@@ -207,7 +392,7 @@ fn compute_tree_size<T>(nodes: &mut [IntervalNode<T>], root_idx: usize) -> usize
 
 // put nodes in van Emde Boas order
 fn veb_order<T>(nodes: &mut [IntervalNode<T>])
-        where T: std::marker::Copy {
+        where T: std::marker::Copy + IntervalMetadata {
 
     // it seems to not matter all that much how this is sorted
     nodes.sort_unstable_by_key(|node| node.first);
@@ -244,17 +429,11 @@ fn veb_order<T>(nodes: &mut [IntervalNode<T>])
         veb_nodes[i] = nodes[idxs[i]];
 
         // update left and right pointers
-        veb_nodes[i].left = if let Some(left) = info[idxs[i]].left {
-            Some(revidx[left])
-        } else {
-            None
-        };
+        veb_nodes[i].left = child_option(
+            info[idxs[i]].left.map(|left| revidx[left]));
 
-        veb_nodes[i].right = if let Some(right) = info[idxs[i]].right {
-            Some(revidx[right])
-        } else {
-            None
-        };
+        veb_nodes[i].right = child_option(
+            info[idxs[i]].right.map(|right| revidx[right]));
     }
 
     // copy reordered nodes back to the original vector
@@ -271,34 +450,31 @@ fn overlaps(first_a: i32, last_a: i32, first_b: i32, last_b: i32) -> bool {
 }
 
 
-fn query_recursion(
-        nodes: &[IntervalNode<()>], root_idx: usize, first: i32, last: i32,
-        count: &mut usize, overlap: &mut usize, visited: &mut usize) {
+fn query_recursion<T, F>(
+        nodes: &[IntervalNode<T>], root_idx: usize, first: i32, last: i32,
+        visited: &mut usize, visit: &mut F)
+        where T: std::marker::Copy + IntervalMetadata, F: FnMut(&IntervalNode<T>) {
     // println!("{} {:?} {:?} {} {}",
         // root_idx, nodes[root_idx].left, nodes[root_idx].right,
         // nodes[root_idx].first, nodes[root_idx].last);
     *visited += 1;
     if overlaps(nodes[root_idx].first, nodes[root_idx].last, first, last) {
-        *count += 1;
-        // println!("hit!")
-        // *overlap +=
-        //     (min(nodes[root_idx].last, last) -
-        //     max(nodes[root_idx].first, first)) as usize;
+        visit(&nodes[root_idx]);
     }
 
-    if let Some(left) = nodes[root_idx].left {
+    if let Some(left) = child_some(nodes[root_idx].left) {
         if overlaps(
                 nodes[left].subtree_first, nodes[left].subtree_last,
                 first, last) {
-            query_recursion(nodes, left, first, last, count, overlap, visited);
+            query_recursion(nodes, left, first, last, visited, visit);
         }
     }
 
-    if let Some(right) = nodes[root_idx].right {
+    if let Some(right) = child_some(nodes[root_idx].right) {
         if overlaps(
                 nodes[right].subtree_first, nodes[right].subtree_last,
                 first, last) {
-            query_recursion(nodes, right, first, last, count, overlap, visited);
+            query_recursion(nodes, right, first, last, visited, visit);
         }
     }
 
@@ -322,13 +498,15 @@ fn query_recursion(
 }
 
 // super simple query which prints every overlap
-fn query(tree: &COITree<()>, first: i32, last: i32) -> (usize, usize, usize) {
+fn query<T>(tree: &COITree<T>, first: i32, last: i32) -> (usize, usize, usize)
+        where T: std::marker::Copy + IntervalMetadata {
     // println!("QUERY");
     let mut count = 0;
-    let mut overlap = 0;
+    let overlap = 0;
     let mut visited = 0;
-    query_recursion(
-        &tree.nodes, 0, first, last, &mut count, &mut overlap, &mut visited);
+    query_recursion(tree.nodes(), 0, first, last, &mut visited, &mut |_node| {
+        count += 1;
+    });
     return (count, overlap, visited);
 }
 
@@ -402,7 +580,7 @@ fn read_bed_file(path: &str) -> Result<HashMap<String, COITree<()>>, GenericErro
             first: first, last: last,
             subtree_first: first,
             subtree_last: last,
-            left: None, right: None, metadata: ()});
+            left: NIL, right: NIL, metadata: ()});
     }
     eprintln!("reading bed: {}s", now.elapsed().as_millis() as f64 / 1000.0);
 
@@ -417,9 +595,69 @@ fn read_bed_file(path: &str) -> Result<HashMap<String, COITree<()>>, GenericErro
 }
 
 
+// Build one serialized, mmap-able `COITree` per sequence name under
+// `out_dir`, along with an `index.tsv` manifest mapping sequence name to
+// file name, so that later runs can `query_indexed_bed_files` without
+// paying for `veb_order` again.
+fn index_bed_file(path: &str, out_dir: &str) -> Result<(), GenericError> {
+    std::fs::create_dir_all(out_dir)?;
+    let trees = read_bed_file(path)?;
+
+    let index_path = std::path::Path::new(out_dir).join("index.tsv");
+    let mut index_file = std::fs::File::create(&index_path)?;
+
+    for (seqname, tree) in &trees {
+        let tree_filename = format!("{}.coitree", seqname);
+        let tree_file = std::fs::File::create(
+            std::path::Path::new(out_dir).join(&tree_filename))?;
+        tree.serialize(tree_file)?;
+        writeln!(index_file, "{}\t{}", seqname, tree_filename)?;
+    }
+
+    return Ok(());
+}
+
+
+// Load the trees written by `index_bed_file`, mapping each one in rather
+// than rebuilding it from the original BED file.
+fn read_indexed_trees(index_dir: &str) -> Result<HashMap<String, COITree<()>>, GenericError> {
+    let index_path = std::path::Path::new(index_dir).join("index.tsv");
+    let index = std::fs::read_to_string(&index_path)?;
+
+    let mut trees = HashMap::<String, COITree<()>>::new();
+    for line in index.lines() {
+        let mut fields = line.splitn(2, '\t');
+        let seqname = fields.next().ok_or_else(|| GenericError::from(
+            io::Error::new(io::ErrorKind::Other, "malformed index line")))?;
+        let tree_filename = fields.next().ok_or_else(|| GenericError::from(
+            io::Error::new(io::ErrorKind::Other, "malformed index line")))?;
+
+        let file = std::fs::File::open(
+            std::path::Path::new(index_dir).join(tree_filename))?;
+        let mmap = unsafe { memmap::Mmap::map(&file)? };
+        let tree = COITree::from_mmap(mmap).map_err(|err| GenericError::from(
+            io::Error::new(io::ErrorKind::Other, err.to_string())))?;
+
+        trees.insert(seqname.to_string(), tree);
+    }
+
+    return Ok(trees);
+}
+
+
 fn query_bed_files(filename_a: &str, filename_b: &str) -> Result<(), GenericError> {
     let tree = read_bed_file(filename_a)?;
+    return query_trees(&tree, filename_b);
+}
+
+
+fn query_indexed_bed_files(index_dir: &str, filename_b: &str) -> Result<(), GenericError> {
+    let tree = read_indexed_trees(index_dir)?;
+    return query_trees(&tree, filename_b);
+}
 
+
+fn query_trees(tree: &HashMap<String, COITree<()>>, filename_b: &str) -> Result<(), GenericError> {
     let mut rdr = csv::ReaderBuilder::new()
         .delimiter(b'\t')
         .has_headers(false)
@@ -471,12 +709,17 @@ fn query_bed_files(filename_a: &str, filename_b: &str) -> Result<(), GenericErro
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
-    if args.len() < 3 {
-        println!("Must specify file name.");
-        std::process::exit(1);
-    }
 
-    let result = query_bed_files(&args[1], &args[2]);
+    let result = match args.get(1).map(String::as_str) {
+        Some("index") if args.len() >= 4 => index_bed_file(&args[2], &args[3]),
+        Some("query-indexed") if args.len() >= 4 => query_indexed_bed_files(&args[2], &args[3]),
+        _ if args.len() >= 3 => query_bed_files(&args[1], &args[2]),
+        _ => {
+            println!("Must specify file name.");
+            std::process::exit(1);
+        }
+    };
+
     if let Err(err) = result {
         println!("error: {}", err)
     }
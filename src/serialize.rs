@@ -0,0 +1,110 @@
+// Zero-copy serialization and mmap loading of prebuilt trees.
+//
+// Building the vEB layout for a large BED file is pure startup overhead
+// that gets repeated on every run. A finished `COITree<T>` can instead be
+// `serialize`d to a file once and later `from_mmap`ed back with no rebuild
+// and no per-node allocation, the way LevelDB memory-maps its sorted
+// tables: write a small header (magic, version, node count, metadata
+// size) followed by the raw `nodes` slice, then on load validate the
+// header and reinterpret the trailing bytes as `&[IntervalNode<T>]`.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::mem::size_of;
+
+use crate::{COITree, IntervalMetadata, IntervalNode, NodeStorage};
+
+const MAGIC: [u8; 4] = *b"COIT";
+const VERSION: u32 = 1;
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct COITreeHeader {
+    magic: [u8; 4],
+    version: u32,
+    node_count: u64,
+    metadata_size: u64,
+}
+
+unsafe impl bytemuck::Zeroable for COITreeHeader {}
+unsafe impl bytemuck::Pod for COITreeHeader {}
+
+/// Reasons `COITree::from_mmap` can reject a byte slice.
+#[derive(Debug)]
+pub enum DeserializeError {
+    TooShort,
+    BadMagic,
+    UnsupportedVersion(u32),
+    MetadataSizeMismatch { expected: usize, found: usize },
+    LengthMismatch,
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeserializeError::TooShort =>
+                write!(f, "serialized tree is too short to contain a header"),
+            DeserializeError::BadMagic =>
+                write!(f, "serialized tree has an invalid magic number"),
+            DeserializeError::UnsupportedVersion(version) =>
+                write!(f, "unsupported serialized tree version {}", version),
+            DeserializeError::MetadataSizeMismatch { expected, found } =>
+                write!(f, "metadata size mismatch: expected {} bytes, found {}", expected, found),
+            DeserializeError::LengthMismatch =>
+                write!(f, "serialized tree length does not match its header"),
+        }
+    }
+}
+
+impl Error for DeserializeError {}
+
+impl<T> COITree<T> where T: std::marker::Copy + IntervalMetadata {
+    /// Serialize this tree to `writer` as a small header followed by the
+    /// raw node slice. The result can later be memory-mapped and loaded
+    /// with `from_mmap` without re-running `veb_order`.
+    pub fn serialize<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let header = COITreeHeader {
+            magic: MAGIC,
+            version: VERSION,
+            node_count: self.nodes().len() as u64,
+            metadata_size: size_of::<T>() as u64,
+        };
+
+        writer.write_all(bytemuck::bytes_of(&header))?;
+        writer.write_all(bytemuck::cast_slice(self.nodes()))?;
+        Ok(())
+    }
+
+    /// Reinterpret a previously `serialize`d, memory-mapped byte slice as a
+    /// `COITree<T>`. Queries run directly against the mapped region; no
+    /// nodes are copied.
+    pub fn from_mmap(mmap: memmap::Mmap) -> Result<COITree<T>, DeserializeError> {
+        let header_size = size_of::<COITreeHeader>();
+        if mmap.len() < header_size {
+            return Err(DeserializeError::TooShort);
+        }
+
+        let header: COITreeHeader = *bytemuck::from_bytes(&mmap[..header_size]);
+
+        if header.magic != MAGIC {
+            return Err(DeserializeError::BadMagic);
+        }
+        if header.version != VERSION {
+            return Err(DeserializeError::UnsupportedVersion(header.version));
+        }
+        if header.metadata_size as usize != size_of::<T>() {
+            return Err(DeserializeError::MetadataSizeMismatch {
+                expected: size_of::<T>(),
+                found: header.metadata_size as usize,
+            });
+        }
+
+        let expected_len = header_size + header.node_count as usize * size_of::<IntervalNode<T>>();
+        if mmap.len() != expected_len {
+            return Err(DeserializeError::LengthMismatch);
+        }
+
+        Ok(COITree { storage: NodeStorage::Mapped(mmap, std::marker::PhantomData) })
+    }
+}
@@ -0,0 +1,60 @@
+
+use coitrees::{COITree, IntervalNode};
+
+extern crate rand;
+extern crate roaring;
+
+use rand::{Rng, thread_rng};
+use roaring::RoaringBitmap;
+
+
+// True iff the two intervals overlap.
+#[inline(always)]
+fn overlaps(first_a: i32, last_a: i32, first_b: i32, last_b: i32) -> bool {
+    return first_a <= last_b && last_a >= first_b;
+}
+
+
+fn random_interval(min_first: i32, max_last: i32, min_len: i32, max_len: i32) -> (i32, i32) {
+    let mut rng = thread_rng();
+    let len = rng.gen_range(min_len, max_len+1);
+    let start = rng.gen_range(min_first, max_last - len + 1);
+    return (start, start+len-1)
+}
+
+
+// `query_bitmap`/`query_count_bitmap` must agree with a brute-force scan
+// that dedupes hits through a `RoaringBitmap` the same way.
+#[test]
+fn query_bitmap_matches_brute_force() {
+    let min_first = 0;
+    let max_last = 1000000;
+    let min_len = 20;
+    let max_len = 2000;
+    let n = 10000;
+
+    let mut b: Vec<IntervalNode<u32>> = Vec::with_capacity(n);
+    for i in 0..n {
+        let (first, last) = random_interval(min_first, max_last, min_len, max_len);
+        b.push(IntervalNode::new(first, last, i as u32));
+    }
+
+    let a = COITree::new(b.clone());
+
+    for _ in 0..1000 {
+        let (query_first, query_last) = random_interval(min_first, max_last, min_len, max_len);
+
+        let mut a_bitmap = RoaringBitmap::new();
+        a.query_bitmap(query_first, query_last, &mut a_bitmap);
+
+        let mut b_bitmap = RoaringBitmap::new();
+        for interval in &b {
+            if overlaps(interval.first, interval.last, query_first, query_last) {
+                b_bitmap.insert(interval.metadata);
+            }
+        }
+
+        assert_eq!(a_bitmap, b_bitmap);
+        assert_eq!(a.query_count_bitmap(query_first, query_last), b_bitmap.len());
+    }
+}
@@ -0,0 +1,120 @@
+
+use coitrees::DynamicCOITree;
+
+extern crate rand;
+use rand::{Rng, thread_rng};
+
+
+// True iff the two intervals overlap.
+#[inline(always)]
+fn overlaps(first_a: i32, last_a: i32, first_b: i32, last_b: i32) -> bool {
+    return first_a <= last_b && last_a >= first_b;
+}
+
+
+fn random_interval(min_first: i32, max_last: i32, min_len: i32, max_len: i32) -> (i32, i32) {
+    let mut rng = thread_rng();
+    let len = rng.gen_range(min_len, max_len+1);
+    let start = rng.gen_range(min_first, max_last - len + 1);
+    return (start, start+len-1)
+}
+
+
+// Interleave inserts, removes, and queries against a `DynamicCOITree` and a
+// brute-force `Vec`, checking after every step that the two agree.
+#[test]
+fn insert_remove_query_matches_brute_force() {
+    let min_first = 0;
+    let max_last = 100000;
+    let min_len = 10;
+    let max_len = 500;
+
+    let mut tree: DynamicCOITree<u32> = DynamicCOITree::new();
+    let mut live: Vec<(i32, i32, u32)> = Vec::new();
+
+    let mut rng = thread_rng();
+    for i in 0..5000u32 {
+        if !live.is_empty() && rng.gen_bool(0.3) {
+            let idx = rng.gen_range(0, live.len());
+            let (first, last, metadata) = live.swap_remove(idx);
+            tree.remove(first, last, metadata);
+        } else {
+            let (first, last) = random_interval(min_first, max_last, min_len, max_len);
+            tree.insert(first, last, i);
+            live.push((first, last, i));
+        }
+
+        if i % 50 == 0 {
+            let (query_first, query_last) = random_interval(
+                min_first, max_last, min_len, max_len);
+
+            let mut a_hits: Vec<u32> = Vec::new();
+            tree.query(query_first, query_last, |node| a_hits.push(node.metadata));
+            a_hits.sort();
+
+            let mut b_hits: Vec<u32> = live.iter()
+                .filter(|(first, last, _)| overlaps(*first, *last, query_first, query_last))
+                .map(|(_, _, metadata)| *metadata)
+                .collect();
+            b_hits.sort();
+
+            assert_eq!(a_hits, b_hits);
+        }
+    }
+}
+
+
+// Regression test: removing an interval after it has been sealed into a
+// level, then reinserting the exact same `(first, last, metadata)` triple,
+// must not resurrect the stale sealed copy alongside the fresh staged one.
+#[test]
+fn reinsert_after_seal_does_not_duplicate() {
+    let mut tree: DynamicCOITree<u32> = DynamicCOITree::new();
+
+    // Push enough distinct intervals to force the staging buffer to seal
+    // into level 0 at least once.
+    for i in 0..100u32 {
+        tree.insert(i as i32 * 10, i as i32 * 10 + 5, i);
+    }
+
+    tree.remove(500, 505, 50);
+    tree.insert(500, 505, 50);
+
+    let mut hits: Vec<u32> = Vec::new();
+    tree.query(500, 505, |node| hits.push(node.metadata));
+
+    assert_eq!(hits, vec![50]);
+}
+
+
+// Regression test: reinserting a value after it has cascaded into a level
+// *above* level 0 must not duplicate it once the reinsert flushes into a
+// fresh level 0, leaving the stale copy behind in the higher level.
+#[test]
+fn reinsert_after_cascade_does_not_duplicate() {
+    let mut tree: DynamicCOITree<u32> = DynamicCOITree::new();
+
+    // Seal two batches of 64 into level 0, which overflows it (capacity 64)
+    // and cascades the merged 128 nodes, including X, up into level 1.
+    tree.insert(500, 505, 0); // X
+    for i in 1..64u32 {
+        tree.insert(i as i32 * 1000, i as i32 * 1000 + 5, i);
+    }
+    for i in 64..128u32 {
+        tree.insert(i as i32 * 1000, i as i32 * 1000 + 5, i);
+    }
+
+    tree.remove(500, 505, 0);
+    tree.insert(500, 505, 0);
+
+    // 63 more distinct inserts flush X into a fresh level 0 without
+    // cascading far enough to touch the stale copy still sitting in level 1.
+    for i in 128..191u32 {
+        tree.insert(i as i32 * 1000, i as i32 * 1000 + 5, i);
+    }
+
+    let mut hits: Vec<u32> = Vec::new();
+    tree.query(500, 505, |node| hits.push(node.metadata));
+
+    assert_eq!(hits, vec![0]);
+}
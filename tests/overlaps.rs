@@ -0,0 +1,88 @@
+
+use coitrees::{COITree, IntervalMetadata, IntervalNode};
+
+extern crate rand;
+extern crate streaming_iterator;
+
+use rand::{Rng, thread_rng};
+use streaming_iterator::StreamingIterator;
+
+
+// True iff the two intervals overlap.
+#[inline(always)]
+fn overlaps(first_a: i32, last_a: i32, first_b: i32, last_b: i32) -> bool {
+    return first_a <= last_b && last_a >= first_b;
+}
+
+
+// Find overlapping intervals by simply checking every single one.
+// We test against this algorithm which we assume to be correct.
+fn brute_force_query<T, F>(
+        intervals: &[IntervalNode<T>], query_first: i32, query_last: i32, mut visit: F)
+            where T: Copy + IntervalMetadata, F: FnMut(&IntervalNode<T>) {
+    for interval in intervals {
+        if overlaps(interval.first, interval.last, query_first, query_last) {
+            visit(interval);
+        }
+    }
+}
+
+
+fn random_interval(min_first: i32, max_last: i32, min_len: i32, max_len: i32) -> (i32, i32) {
+    let mut rng = thread_rng();
+    let len = rng.gen_range(min_len, max_len+1);
+    let start = rng.gen_range(min_first, max_last - len + 1);
+    return (start, start+len-1)
+}
+
+
+// Run queries against both `COITree::overlaps` and brute force and check
+// that they surface the same metadata, regardless of order.
+fn check_overlaps_queries(n: usize, num_queries: usize) {
+    let min_first = 0;
+    let max_last = 1000000;
+    let min_len = 20;
+    let max_len = 2000;
+
+    let mut b: Vec<IntervalNode<u32>> = Vec::with_capacity(n);
+    for i in 0..n {
+        let (first, last) = random_interval(min_first, max_last, min_len, max_len);
+        b.push(IntervalNode::new(first, last, i as u32));
+    }
+
+    let a = COITree::new(b.clone());
+
+    for _ in 0..num_queries {
+        let (query_first, query_last) = random_interval(min_first, max_last, min_len, max_len);
+
+        let mut a_hits: Vec<u32> = Vec::new();
+        let mut iter = a.overlaps(query_first, query_last);
+        while let Some(node) = iter.next() {
+            a_hits.push(node.metadata);
+        }
+
+        let mut b_hits: Vec<u32> = Vec::new();
+        brute_force_query(&b, query_first, query_last, |node| {
+            b_hits.push(node.metadata)
+        });
+
+        a_hits.sort();
+        b_hits.sort();
+
+        assert_eq!(a_hits, b_hits);
+    }
+}
+
+
+#[test]
+fn overlaps_small_trees() {
+    for n in 1..16 {
+        check_overlaps_queries(n, 1000);
+    }
+}
+
+
+#[test]
+fn overlaps_medium_tree() {
+    check_overlaps_queries(10000, 1000);
+}
@@ -96,6 +96,53 @@ fn check_queries(a: &COITree<u32>, b: &[IntervalNode<u32>], queries: &mut [(i32,
 }
 
 
+// Brute force count of query positions covered by >= 1 interval.
+fn brute_force_covered_bases<T>(
+        intervals: &[IntervalNode<T>], query_first: i32, query_last: i32) -> i64
+            where T: Copy {
+    let mut covered = 0i64;
+    for pos in query_first..=query_last {
+        if intervals.iter().any(|interval| overlaps(interval.first, interval.last, pos, pos)) {
+            covered += 1;
+        }
+    }
+    return covered;
+}
+
+
+// Brute force sum, over every overlapping interval, of the number of query
+// positions it covers.
+fn brute_force_overlap_bases<T>(
+        intervals: &[IntervalNode<T>], query_first: i32, query_last: i32) -> i64
+            where T: Copy {
+    let mut total = 0i64;
+    for interval in intervals {
+        if overlaps(interval.first, interval.last, query_first, query_last) {
+            total += (interval.last.min(query_last) - interval.first.max(query_first) + 1) as i64;
+        }
+    }
+    return total;
+}
+
+
+// Brute force per-position depth over the query span.
+fn brute_force_depth_histogram<T>(
+        intervals: &[IntervalNode<T>], query_first: i32, query_last: i32) -> Vec<u32>
+            where T: Copy {
+    let mut histogram = vec![0u32; (query_last - query_first + 1) as usize];
+    for interval in intervals {
+        if overlaps(interval.first, interval.last, query_first, query_last) {
+            let start = interval.first.max(query_first) - query_first;
+            let end = interval.last.min(query_last) - query_first;
+            for depth in &mut histogram[start as usize..=end as usize] {
+                *depth += 1;
+            }
+        }
+    }
+    return histogram;
+}
+
+
 fn check_coverage(a: &COITree<u32>, b: &[IntervalNode<u32>], queries: &mut [(i32, i32)]) {
     for (query_first, query_last) in queries {
         let a_cover = a.coverage(*query_first, *query_last);
@@ -124,6 +171,22 @@ fn check_count_queries(a: &COITree<u32>, b: &[IntervalNode<u32>], queries: &mut
 }
 
 
+fn check_covered_and_overlap_bases(
+        a: &COITree<u32>, b: &[IntervalNode<u32>], queries: &mut [(i32, i32)]) {
+    for (query_first, query_last) in queries {
+        assert_eq!(
+            a.covered_bases(*query_first, *query_last),
+            brute_force_covered_bases(b, *query_first, *query_last));
+        assert_eq!(
+            a.overlap_bases(*query_first, *query_last),
+            brute_force_overlap_bases(b, *query_first, *query_last));
+        assert_eq!(
+            a.depth_histogram(*query_first, *query_last),
+            brute_force_depth_histogram(b, *query_first, *query_last));
+    }
+}
+
+
 // check SortedQuerent queries against brute force
 fn check_sorted_querent_queries(
         a: &COITree<u32>, b: &[IntervalNode<u32>], queries: &mut [(i32, i32)]) {
@@ -242,6 +305,7 @@ fn query_empty_tree() {
         check_random_queries_default(0, 1000, check);
     }
     check_random_queries_default(0, 1000, check_coverage);
+    check_random_queries_default(0, 1000, check_covered_and_overlap_bases);
 }
 
 #[test]
@@ -251,6 +315,7 @@ fn query_small_trees() {
             check_random_queries_default(n, 1000, check);
         }
         check_random_queries_default(n, 1000, check_coverage);
+        check_random_queries_default(n, 1000, check_covered_and_overlap_bases);
     }
 }
 
@@ -260,6 +325,7 @@ fn query_medium_tree() {
         check_random_queries_default(10000, 1000, check);
     }
     check_random_queries_default(10000, 1000, check_coverage);
+    check_random_queries_default(10000, 1000, check_covered_and_overlap_bases);
 }
 
 
@@ -271,6 +337,8 @@ fn query_singeton_intervals() {
     }
     check_random_queries(10000, 1000, 1000, 1, 1, 1,    1, check_coverage);
     check_random_queries(10000, 1000, 1000, 1, 1, 10, 100, check_coverage);
+    check_random_queries(10000, 1000, 1000, 1, 1, 1,    1, check_covered_and_overlap_bases);
+    check_random_queries(10000, 1000, 1000, 1, 1, 10, 100, check_covered_and_overlap_bases);
 }
 
 
@@ -0,0 +1,66 @@
+
+use coitrees::{COITree, IntervalNode};
+
+extern crate memmap;
+extern crate rand;
+extern crate streaming_iterator;
+
+use rand::{Rng, thread_rng};
+use streaming_iterator::StreamingIterator;
+
+
+fn random_interval(min_first: i32, max_last: i32, min_len: i32, max_len: i32) -> (i32, i32) {
+    let mut rng = thread_rng();
+    let len = rng.gen_range(min_len, max_len+1);
+    let start = rng.gen_range(min_first, max_last - len + 1);
+    return (start, start+len-1)
+}
+
+
+// A tree serialized to a file and loaded back through `from_mmap` must
+// answer `overlaps` queries identically to the original, in-memory tree.
+#[test]
+fn serialize_from_mmap_round_trip() {
+    let min_first = 0;
+    let max_last = 1000000;
+    let min_len = 20;
+    let max_len = 2000;
+    let n = 10000;
+
+    let mut nodes: Vec<IntervalNode<u32>> = Vec::with_capacity(n);
+    for i in 0..n {
+        let (first, last) = random_interval(min_first, max_last, min_len, max_len);
+        nodes.push(IntervalNode::new(first, last, i as u32));
+    }
+
+    let tree = COITree::new(nodes);
+
+    let path = std::env::temp_dir().join(format!(
+        "coitrees-serialize-round-trip-{}.coitree", std::process::id()));
+    tree.serialize(std::fs::File::create(&path).unwrap()).unwrap();
+
+    let mapped_file = std::fs::File::open(&path).unwrap();
+    let mmap = unsafe { memmap::Mmap::map(&mapped_file).unwrap() };
+    let mapped_tree = COITree::from_mmap(mmap).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    for _ in 0..1000 {
+        let (query_first, query_last) = random_interval(min_first, max_last, min_len, max_len);
+
+        let mut expected: Vec<u32> = Vec::new();
+        let mut iter = tree.overlaps(query_first, query_last);
+        while let Some(node) = iter.next() {
+            expected.push(node.metadata);
+        }
+
+        let mut actual: Vec<u32> = Vec::new();
+        let mut iter = mapped_tree.overlaps(query_first, query_last);
+        while let Some(node) = iter.next() {
+            actual.push(node.metadata);
+        }
+
+        expected.sort();
+        actual.sort();
+        assert_eq!(expected, actual);
+    }
+}